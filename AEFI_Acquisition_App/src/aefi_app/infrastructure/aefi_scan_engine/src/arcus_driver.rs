@@ -1,6 +1,8 @@
 use serialport::{SerialPort, SerialPortType};
 use std::io::{self, Read, Write};
-use std::time::Duration;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,10 +15,150 @@ pub enum ArcusError {
     Timeout,
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("Controller reported an error: {0}")]
+    ControllerError(String),
+}
+
+/// A single request understood by the controller, independent of how it is
+/// wire-formatted or what shape of reply it expects.
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    GetPosition(char),
+    MoveTo(char, i32),
+    Status,
+    SetHighSpeed(char, i32),
+    ResetCounter(u8),
+    ReadCounter(u8),
+    SetCompareTable(char, i32, i32, u32),
+    ArmTrigger,
+    DisarmTrigger,
+    FirePulseOn,
+    FirePulseOff,
+}
+
+/// What a command's reply should look like, so `transact` can validate it
+/// instead of callers each doing their own ad-hoc parsing.
+enum ResponseShape {
+    Numeric,
+    StatusBitmask,
+}
+
+/// A validated reply, typed to match the `ResponseShape` its `Command` asked for.
+enum Response {
+    Numeric(i32),
+    StatusBitmask(i32),
+    /// The command doesn't get a reply; it was only written to the port.
+    None,
+}
+
+impl Command {
+    fn format(self) -> String {
+        match self {
+            Command::GetPosition(axis) => format!("P{}", axis),
+            Command::MoveTo(axis, position) => format!("{}{}", axis, position),
+            Command::Status => "MST".to_string(),
+            Command::SetHighSpeed(axis, speed) => format!("HS{}={}", axis, speed),
+            Command::ResetCounter(channel) => format!("CTR{}=0", channel),
+            Command::ReadCounter(channel) => format!("CTR{}", channel),
+            Command::SetCompareTable(axis, start, step, count) => {
+                format!("CMPTBL{}={},{},{}", axis, start, step, count)
+            }
+            Command::ArmTrigger => "ARM".to_string(),
+            Command::DisarmTrigger => "DISARM".to_string(),
+            Command::FirePulseOn => "OUT1=1".to_string(),
+            Command::FirePulseOff => "OUT1=0".to_string(),
+        }
+    }
+
+    /// `None` for commands the Arcus ASCII protocol executes fire-and-forget
+    /// (motion and output commands don't ack): `transact` writes these and
+    /// returns immediately instead of blocking on a reply that never comes.
+    fn expected_shape(self) -> Option<ResponseShape> {
+        match self {
+            Command::GetPosition(_) => Some(ResponseShape::Numeric),
+            Command::Status => Some(ResponseShape::StatusBitmask),
+            Command::ReadCounter(_) => Some(ResponseShape::Numeric),
+            Command::MoveTo(..)
+            | Command::SetHighSpeed(..)
+            | Command::ResetCounter(_)
+            | Command::SetCompareTable(..)
+            | Command::ArmTrigger
+            | Command::DisarmTrigger
+            | Command::FirePulseOn
+            | Command::FirePulseOff => None,
+        }
+    }
+}
+
+/// Reads from `port` until it errors out, parsing `\r`/`\n`-terminated frames
+/// and forwarding each complete frame to `tx`. Runs on its own thread so that
+/// `ArcusController` never blocks a caller on raw byte-at-a-time reads; it
+/// exits (dropping `tx`) as soon as the port is closed or hits a fatal error.
+fn run_reader(mut port: Box<dyn SerialPort>, tx: mpsc::Sender<String>) {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut temp_buf = [0u8; 1];
+
+    loop {
+        match port.read(&mut temp_buf) {
+            Ok(n) if n > 0 => {
+                let byte = temp_buf[0];
+                if byte == b'\n' || byte == b'\r' {
+                    if !buffer.is_empty() {
+                        let frame = String::from_utf8_lossy(&buffer).to_string();
+                        buffer.clear();
+                        if tx.send(frame).is_err() {
+                            return; // Receiver dropped, controller has gone away.
+                        }
+                    }
+                } else {
+                    buffer.push(byte);
+                }
+            }
+            Ok(_) => continue,
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(_) => return, // Port closed or hard I/O failure; let the thread die.
+        }
+    }
+}
+
+/// A record of one fired trigger, useful for checking the jitter between the
+/// commanded target and where the axis actually was when the pulse went out.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerRecord {
+    pub n: u32,
+    pub target: i32,
+    pub actual_pos: i32,
+    pub host_timestamp_us: u128,
+}
+
+/// Software model of a hardware position-compare table: fire a trigger every
+/// `step` encoder counts starting at `start`, for `count` targets.
+struct CompareTable {
+    start: i32,
+    step: i32,
+    count: u32,
+    next_index: u32,
 }
 
 pub struct ArcusController {
     port: Box<dyn SerialPort>,
+    frames: Receiver<String>,
+    // Kept alive for the lifetime of the controller and never joined
+    // explicitly; the thread exits on its own once `frames` has been dropped
+    // (on the next frame it tries to forward) or its port read fails.
+    _reader_handle: JoinHandle<()>,
+    compare_table: Option<CompareTable>,
+    pulse_width_us: u64,
+    armed: bool,
+    retry_count: u32,
+    retry_base_delay: Duration,
+}
+
+pub(crate) fn host_timestamp_us() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
 }
 
 impl ArcusController {
@@ -24,8 +166,29 @@ impl ArcusController {
         let port = serialport::new(port_name, baud_rate)
             .timeout(Duration::from_millis(100))
             .open()?;
-        
-        Ok(ArcusController { port })
+        let reader_port = port.try_clone()?;
+
+        let (tx, rx) = mpsc::channel();
+        let reader_handle = thread::spawn(move || run_reader(reader_port, tx));
+
+        Ok(ArcusController {
+            port,
+            frames: rx,
+            _reader_handle: reader_handle,
+            compare_table: None,
+            pulse_width_us: 0,
+            armed: false,
+            retry_count: 3,
+            retry_base_delay: Duration::from_millis(50),
+        })
+    }
+
+    /// Overrides the number of retries `transact` attempts on a `Timeout`
+    /// before giving up, and the initial delay it backs off with (doubled on
+    /// each subsequent retry). Defaults to 3 retries starting at 50ms.
+    pub fn set_retry_policy(&mut self, retry_count: u32, base_delay: Duration) {
+        self.retry_count = retry_count;
+        self.retry_base_delay = base_delay;
     }
 
     fn send_command(&mut self, cmd: &str) -> Result<(), ArcusError> {
@@ -34,60 +197,202 @@ impl ArcusController {
         Ok(())
     }
 
+    /// Waits for the next complete frame parsed by the reader thread. This
+    /// never touches the raw port directly, so it cleanly distinguishes "no
+    /// data yet" (`ArcusError::Timeout`) from a dead link (`ArcusError::Io`).
     fn read_response(&mut self) -> Result<String, ArcusError> {
-        let mut buffer: Vec<u8> = Vec::new();
-        let mut temp_buf = [0u8; 1];
-        
-        // Simple read until newline or timeout
-        // In production, use a more robust buffered reader
-        loop {
-            match self.port.read(&mut temp_buf) {
-                Ok(n) if n > 0 => {
-                    let byte = temp_buf[0];
-                    if byte == b'\n' || byte == b'\r' {
-                        if !buffer.is_empty() {
-                            break;
-                        }
-                    } else {
-                        buffer.push(byte);
-                    }
-                }
-                Ok(_) => continue,
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                    return Err(ArcusError::Timeout);
+        match self.frames.recv_timeout(Duration::from_millis(500)) {
+            Ok(frame) => Ok(frame),
+            Err(RecvTimeoutError::Timeout) => Err(ArcusError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(ArcusError::Io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "reader thread exited",
+            ))),
+        }
+    }
+
+    /// Sends `cmd`. Commands that expect a reply (numeric or status bitmask)
+    /// read exactly one framed reply and validate it against that shape,
+    /// surfacing a controller-reported error string as
+    /// `ArcusError::ControllerError` instead of silently parsing it as 0,
+    /// and retrying up to `retry_count` times with exponential backoff
+    /// starting at `retry_base_delay` on `Timeout`, since flaky USB-serial
+    /// links drop the occasional frame. Commands that don't ack (motion and
+    /// output commands, per the Arcus ASCII protocol) are written and
+    /// returned from immediately, matching the original fire-and-forget
+    /// behavior.
+    fn transact(&mut self, cmd: Command) -> Result<Response, ArcusError> {
+        let formatted = cmd.format();
+
+        let Some(shape) = cmd.expected_shape() else {
+            self.send_command(&formatted)?;
+            return Ok(Response::None);
+        };
+
+        let mut delay = self.retry_base_delay;
+        for attempt in 0..=self.retry_count {
+            self.send_command(&formatted)?;
+            match self.read_response() {
+                Ok(frame) => return Self::parse_response(shape, frame),
+                Err(ArcusError::Timeout) if attempt < self.retry_count => {
+                    thread::sleep(delay);
+                    delay *= 2;
                 }
-                Err(e) => return Err(ArcusError::Io(e)),
+                Err(e) => return Err(e),
             }
         }
-        
-        Ok(String::from_utf8_lossy(&buffer).to_string())
+        Err(ArcusError::Timeout)
+    }
+
+    fn parse_response(shape: ResponseShape, frame: String) -> Result<Response, ArcusError> {
+        let trimmed = frame.trim();
+        // Arcus controllers prefix error replies with '?' followed by a
+        // reason string instead of the expected value.
+        if let Some(reason) = trimmed.strip_prefix('?') {
+            return Err(ArcusError::ControllerError(reason.to_string()));
+        }
+
+        match shape {
+            ResponseShape::Numeric => trimmed
+                .parse::<i32>()
+                .map(Response::Numeric)
+                .map_err(|_| ArcusError::InvalidResponse(frame)),
+            ResponseShape::StatusBitmask => trimmed
+                .parse::<i32>()
+                .map(Response::StatusBitmask)
+                .map_err(|_| ArcusError::InvalidResponse(frame)),
+        }
     }
 
     pub fn get_position(&mut self, axis: char) -> Result<i32, ArcusError> {
-        self.send_command(&format!("P{}", axis))?;
-        let resp = self.read_response()?;
-        // Response format example: "1000"
-        resp.trim().parse::<i32>().map_err(|_| ArcusError::InvalidResponse(resp))
+        match self.transact(Command::GetPosition(axis))? {
+            Response::Numeric(pos) => Ok(pos),
+            _ => unreachable!("Command::GetPosition always parses to Response::Numeric"),
+        }
     }
 
     pub fn move_to(&mut self, axis: char, position: i32) -> Result<(), ArcusError> {
-        // Absolute move
-        self.send_command(&format!("{}{}", axis, position))?;
+        self.transact(Command::MoveTo(axis, position))?;
         Ok(())
     }
 
     pub fn is_moving(&mut self) -> Result<bool, ArcusError> {
-        // Check status, implementation depends on specific Arcus model
-        // Assuming "MST" returns status bitmask
-        self.send_command("MST")?;
-        let resp = self.read_response()?;
-        let status = resp.trim().parse::<i32>().unwrap_or(0);
-        // Bit 0 usually indicates moving
-        Ok((status & 1) != 0)
-    }
-    
+        match self.transact(Command::Status)? {
+            // Bit 0 usually indicates moving.
+            Response::StatusBitmask(status) => Ok((status & 1) != 0),
+            _ => unreachable!("Command::Status always parses to Response::StatusBitmask"),
+        }
+    }
+
     pub fn set_high_speed(&mut self, axis: char, speed: i32) -> Result<(), ArcusError> {
-        self.send_command(&format!("HS{}={}", axis, speed))?;
+        self.transact(Command::SetHighSpeed(axis, speed))?;
+        Ok(())
+    }
+
+    /// Clears the accumulated pulse count on the given detector-input
+    /// counter channel, ready for a new dwell-time acquisition.
+    pub fn reset_counter(&mut self, channel: u8) -> Result<(), ArcusError> {
+        self.transact(Command::ResetCounter(channel))?;
+        Ok(())
+    }
+
+    /// Reads the accumulated TTL pulse count on the given edge counter
+    /// channel since it was last reset.
+    pub fn read_counter(&mut self, channel: u8) -> Result<i32, ArcusError> {
+        match self.transact(Command::ReadCounter(channel))? {
+            Response::Numeric(counts) => Ok(counts),
+            _ => unreachable!("Command::ReadCounter always parses to Response::Numeric"),
+        }
+    }
+
+    /// Loads the position-compare table for `axis`: a trigger fires every
+    /// `step` encoder counts starting at `start`, for `count` targets.
+    /// Issues the hardware compare-register command for controllers that
+    /// support one; controllers that don't simply ignore it and fall back to
+    /// the software emulation in `poll_trigger`.
+    pub fn set_compare_table(
+        &mut self,
+        axis: char,
+        start: i32,
+        step: i32,
+        count: u32,
+    ) -> Result<(), ArcusError> {
+        self.transact(Command::SetCompareTable(axis, start, step, count))?;
+        self.compare_table = Some(CompareTable {
+            start,
+            step,
+            count,
+            next_index: 0,
+        });
+        Ok(())
+    }
+
+    /// Arms triggering: the configured compare table starts over from its
+    /// first target and TTL pulses of `pulse_width_us` fire as targets are
+    /// crossed.
+    pub fn arm_trigger(&mut self, pulse_width_us: u64) -> Result<(), ArcusError> {
+        self.transact(Command::ArmTrigger)?;
+        self.pulse_width_us = pulse_width_us;
+        self.armed = true;
+        if let Some(table) = &mut self.compare_table {
+            table.next_index = 0;
+        }
+        Ok(())
+    }
+
+    pub fn disarm_trigger(&mut self) -> Result<(), ArcusError> {
+        self.transact(Command::DisarmTrigger)?;
+        self.armed = false;
+        Ok(())
+    }
+
+    /// Software emulation of a hardware position-compare output: given the
+    /// latest polled `pos`, fires a trigger for every configured target the
+    /// axis has crossed since the last call (`forward` selects `pos >=
+    /// target` vs. `pos <= target`), so an overshoot that skips past more
+    /// than one target in a single read interval still fires all of them
+    /// rather than silently dropping some. Returns a record per trigger
+    /// fired, in order.
+    pub fn poll_trigger(&mut self, pos: i32, forward: bool) -> Result<Vec<TriggerRecord>, ArcusError> {
+        let mut fired = Vec::new();
+        if !self.armed {
+            return Ok(fired);
+        }
+
+        loop {
+            let next = match &self.compare_table {
+                Some(table) if table.next_index < table.count => {
+                    Some((table.next_index, table.start + table.next_index as i32 * table.step))
+                }
+                _ => None,
+            };
+            let Some((index, target)) = next else {
+                break;
+            };
+            let crossed = if forward { pos >= target } else { pos <= target };
+            if !crossed {
+                break;
+            }
+
+            self.fire_pulse()?;
+            fired.push(TriggerRecord {
+                n: index,
+                target,
+                actual_pos: pos,
+                host_timestamp_us: host_timestamp_us(),
+            });
+            if let Some(table) = &mut self.compare_table {
+                table.next_index += 1;
+            }
+        }
+
+        Ok(fired)
+    }
+
+    fn fire_pulse(&mut self) -> Result<(), ArcusError> {
+        self.transact(Command::FirePulseOn)?;
+        thread::sleep(Duration::from_micros(self.pulse_width_us));
+        self.transact(Command::FirePulseOff)?;
         Ok(())
     }
 }
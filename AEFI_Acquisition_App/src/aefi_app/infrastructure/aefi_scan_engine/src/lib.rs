@@ -1,9 +1,37 @@
 mod arcus_driver;
-use arcus_driver::ArcusController;
+use arcus_driver::{ArcusController, ArcusError};
 use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use std::thread;
 
+/// Commands `axis` to `target` and polls its position until it settles
+/// within `tolerance`, returning the last polled position. Shared by every
+/// scan mode that steps an axis to a point and waits for it to arrive
+/// before recording or triggering.
+fn move_and_settle(ctrl: &mut ArcusController, axis: char, target: i32, tolerance: i32) -> Result<i32, ArcusError> {
+    ctrl.move_to(axis, target)?;
+    loop {
+        let pos = ctrl.get_position(axis)?;
+        if (pos - target).abs() < tolerance {
+            return Ok(pos);
+        }
+        // Ultra-short sleep to prevent CPU hogging, but keep high responsiveness.
+        thread::sleep(Duration::from_micros(100));
+    }
+}
+
+/// Dwell-time edge-counter acquisition at an already-settled point: clears
+/// the detector counter on `channel`, waits `dwell_us`, then reads back the
+/// accumulated TTL pulse count. Shared by every scan mode that records an
+/// intensity per point rather than just a position.
+fn measure_dwell(ctrl: &mut ArcusController, channel: u8, dwell_us: u64) -> Result<i32, ArcusError> {
+    ctrl.reset_counter(channel)?;
+    thread::sleep(Duration::from_micros(dwell_us));
+    ctrl.read_counter(channel)
+}
+
 /// A Python module implemented in Rust.
 #[pymodule]
 fn aefi_scan_engine(_py: Python, m: &PyModule) -> PyResult<()> {
@@ -16,6 +44,8 @@ struct RustScanExecutor {
     port_name: String,
     baud_rate: u32,
     controller: Option<ArcusController>,
+    stop_requested: Arc<AtomicBool>,
+    counter_channel: u8,
 }
 
 #[pymethods]
@@ -26,9 +56,25 @@ impl RustScanExecutor {
             port_name,
             baud_rate,
             controller: None,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            counter_channel: 0,
         }
     }
 
+    /// Selects which detector-input edge-counter channel the dwell-time
+    /// acquisition in `start_scan`/`start_raster_scan`/`start_scan_streaming`
+    /// reads from. Defaults to channel 0.
+    fn set_counter_channel(&mut self, channel: u8) {
+        self.counter_channel = channel;
+    }
+
+    /// Cooperatively halts a running `start_scan_streaming` loop: the scan
+    /// checks this flag once per point, right after each GIL-released move,
+    /// so this can be called from Python while a scan is in progress.
+    fn request_stop(&self) {
+        self.stop_requested.store(true, Ordering::Relaxed);
+    }
+
     fn connect(&mut self) -> PyResult<String> {
         match ArcusController::connect(&self.port_name, self.baud_rate) {
             Ok(ctrl) => {
@@ -49,48 +95,228 @@ impl RustScanExecutor {
         }
     }
 
-    fn start_scan(&mut self, x_min: i32, x_max: i32, step: i32) -> PyResult<Vec<(i32, i32)>> {
+    fn start_scan(
+        &mut self,
+        x_min: i32,
+        x_max: i32,
+        step: i32,
+        dwell_us: u64,
+    ) -> PyResult<Vec<(i32, i32, i32, u64)>> {
         if let Some(ctrl) = &mut self.controller {
+            let to_io_err = |e: ArcusError| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string());
             let mut results = Vec::new();
             let mut current_x = x_min;
 
-            // Move to start
-            ctrl.move_to('x', x_min)
-                .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-            
-            // Wait for arrival at start (simple polling for now)
-            while let Ok(pos) = ctrl.get_position('x') {
-                if (pos - x_min).abs() < 10 { break; }
-                thread::sleep(Duration::from_millis(10));
-            }
+            // Move to start and wait for arrival.
+            move_and_settle(ctrl, 'x', x_min, 10).map_err(to_io_err)?;
 
             // Scan loop
             while current_x <= x_max {
-                // 1. Move to next position
-                ctrl.move_to('x', current_x)
-                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+                let pos = move_and_settle(ctrl, 'x', current_x, 5).map_err(to_io_err)?;
+                let counts = measure_dwell(ctrl, self.counter_channel, dwell_us).map_err(to_io_err)?;
+                results.push((current_x, pos, counts, dwell_us));
 
-                // 2. Busy wait / Poll for position (Critical loop)
-                // In a real flyscan, we might not wait for full stop, but trigger when in window
+                current_x += step;
+            }
+
+            Ok(results)
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Not connected"))
+        }
+    }
+
+    /// Two-axis serpentine (boustrophedon) raster scan: `x` sweeps forward
+    /// on even rows and reverse on odd rows while `y` steps between rows, so
+    /// there is never a long fly-back move (and its backlash) at the end of
+    /// a line. Reuses the same per-point settle and dwell-acquisition logic
+    /// as `start_scan` and returns the full `(x, y, counts, dwell_us)` grid
+    /// of visited points in scan order.
+    fn start_raster_scan(
+        &mut self,
+        x_min: i32,
+        x_max: i32,
+        x_step: i32,
+        y_min: i32,
+        y_max: i32,
+        y_step: i32,
+        dwell_us: u64,
+    ) -> PyResult<Vec<(i32, i32, i32, u64)>> {
+        if x_step <= 0 || x_max < x_min {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "start_raster_scan requires x_step > 0 and x_max >= x_min",
+            ));
+        }
+        if y_step <= 0 || y_max < y_min {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "start_raster_scan requires y_step > 0 and y_max >= y_min",
+            ));
+        }
+
+        if let Some(ctrl) = &mut self.controller {
+            let to_io_err = |e: ArcusError| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string());
+            let mut results = Vec::new();
+            // Last column on the x_step grid that does not pass x_max, so
+            // reverse rows retrace exactly the columns of forward rows.
+            let x_last = x_min + (x_max - x_min) / x_step * x_step;
+
+            let mut y = y_min;
+            let mut row = 0u32;
+            while y <= y_max {
+                move_and_settle(ctrl, 'y', y, 10).map_err(to_io_err)?;
+                let forward = row % 2 == 0;
+
+                let mut x = if forward { x_min } else { x_last };
                 loop {
-                    let pos = ctrl.get_position('x')
-                        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-                    
-                    if (pos - current_x).abs() < 5 {
-                        // 3. Trigger Acquisition (Placeholder)
-                        // In real impl: set TTL high, wait, set low
-                        // For now, just record position
-                        results.push((current_x, pos));
+                    let actual_x = move_and_settle(ctrl, 'x', x, 5).map_err(to_io_err)?;
+                    let actual_y = ctrl.get_position('y').map_err(to_io_err)?;
+                    let counts = measure_dwell(ctrl, self.counter_channel, dwell_us).map_err(to_io_err)?;
+                    results.push((actual_x, actual_y, counts, dwell_us));
+
+                    if forward {
+                        if x >= x_last {
+                            break;
+                        }
+                        x += x_step;
+                    } else {
+                        if x <= x_min {
+                            break;
+                        }
+                        x -= x_step;
+                    }
+                }
+
+                y += y_step;
+                row += 1;
+            }
+
+            Ok(results)
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Not connected"))
+        }
+    }
+
+    /// Continuous-velocity flyscan: the `x` axis is commanded to move at
+    /// `speed` from `x_min` to `x_max` without stopping, and a TTL pulse of
+    /// `pulse_width_us` fires every `step` encoder counts via the
+    /// position-compare trigger instead of stopping-and-settling at each
+    /// point. Returns one `(n, target, actual_pos, host_timestamp_us)` record
+    /// per trigger fired, so jitter between the commanded and actual fire
+    /// position is visible.
+    fn start_flyscan(
+        &mut self,
+        x_min: i32,
+        x_max: i32,
+        step: i32,
+        speed: i32,
+        pulse_width_us: u64,
+    ) -> PyResult<Vec<(u32, i32, i32, u128)>> {
+        if step <= 0 || x_max < x_min {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "start_flyscan requires step > 0 and x_max >= x_min",
+            ));
+        }
+
+        if let Some(ctrl) = &mut self.controller {
+            let to_io_err = |e: arcus_driver::ArcusError| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string())
+            };
+
+            // Move to the start and wait for arrival before arming triggers.
+            move_and_settle(ctrl, 'x', x_min, 10).map_err(to_io_err)?;
+
+            let count = ((x_max - x_min) / step) as u32 + 1;
+            ctrl.set_compare_table('x', x_min, step, count).map_err(to_io_err)?;
+            ctrl.arm_trigger(pulse_width_us).map_err(to_io_err)?;
+
+            // Command the constant-velocity move; the compare table fires
+            // triggers as the axis sweeps through it, with no stop-and-settle
+            // per point.
+            ctrl.set_high_speed('x', speed).map_err(to_io_err)?;
+            ctrl.move_to('x', x_max).map_err(to_io_err)?;
+
+            // Run the polling loop as a fallible closure so the controller
+            // is always disarmed afterwards, even if a read errors mid-scan.
+            let scan_result: Result<Vec<(u32, i32, i32, u128)>, arcus_driver::ArcusError> = (|| {
+                let mut records = Vec::new();
+                loop {
+                    let pos = ctrl.get_position('x')?;
+                    for t in ctrl.poll_trigger(pos, true)? {
+                        records.push((t.n, t.target, t.actual_pos, t.host_timestamp_us));
+                    }
+
+                    if (pos - x_max).abs() < 5 && !ctrl.is_moving()? {
                         break;
                     }
-                    // Ultra-short sleep to prevent CPU hogging, but keep high responsiveness
-                    thread::sleep(Duration::from_micros(100)); 
+                    thread::sleep(Duration::from_micros(100));
+                }
+                Ok(records)
+            })();
+            let disarm_result = ctrl.disarm_trigger();
+
+            match scan_result {
+                Ok(records) => disarm_result.map(|_| records).map_err(to_io_err),
+                Err(e) => Err(to_io_err(e)),
+            }
+        } else {
+            Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Not connected"))
+        }
+    }
+
+    /// Streaming variant of `start_scan`: instead of blocking until the
+    /// whole scan completes and handing back one `Vec`, `callback` is
+    /// invoked with `(index, x, actual_pos, counts, dwell_us,
+    /// host_timestamp_us)` as each point is acquired, so Python gets live
+    /// feedback. Motion and the dwell-time acquisition run under
+    /// `Python::allow_threads` so the GIL is only held to fire the callback,
+    /// and the loop checks `stop_requested` every iteration so
+    /// `request_stop()` can cancel it; `callback` returning `True` also ends
+    /// the scan early. Returns the number of points acquired before
+    /// finishing or being stopped.
+    fn start_scan_streaming(
+        &mut self,
+        py: Python,
+        x_min: i32,
+        x_max: i32,
+        step: i32,
+        dwell_us: u64,
+        callback: Py<PyAny>,
+    ) -> PyResult<usize> {
+        if let Some(ctrl) = &mut self.controller {
+            let to_io_err = |e: ArcusError| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string());
+            let counter_channel = self.counter_channel;
+            self.stop_requested.store(false, Ordering::Relaxed);
+
+            py.allow_threads(|| move_and_settle(ctrl, 'x', x_min, 10))
+                .map_err(to_io_err)?;
+
+            let mut current_x = x_min;
+            let mut index = 0usize;
+            while current_x <= x_max {
+                if self.stop_requested.load(Ordering::Relaxed) {
+                    break;
                 }
 
+                let (pos, counts) = py
+                    .allow_threads(|| -> Result<(i32, i32), ArcusError> {
+                        let pos = move_and_settle(ctrl, 'x', current_x, 5)?;
+                        let counts = measure_dwell(ctrl, counter_channel, dwell_us)?;
+                        Ok((pos, counts))
+                    })
+                    .map_err(to_io_err)?;
+                let timestamp = arcus_driver::host_timestamp_us();
+
+                let result = callback.call1(py, (index, current_x, pos, counts, dwell_us, timestamp))?;
+                let requested_stop = result.extract::<bool>(py).unwrap_or(false);
+
+                index += 1;
                 current_x += step;
+
+                if requested_stop || self.stop_requested.load(Ordering::Relaxed) {
+                    break;
+                }
             }
-            
-            Ok(results)
+
+            Ok(index)
         } else {
             Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Not connected"))
         }